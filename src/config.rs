@@ -0,0 +1,359 @@
+// Runtime configuration: flat env vars for static settings, plus an optional
+// layered TOML file (CONFIG_PATH) for thresholds that should flex with
+// conditions instead of staying fixed for the life of the process.
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+
+use crate::ids::Snowflake;
+use crate::state::StateMachine;
+use crate::throttle::{RateLimit, Throttle};
+
+#[derive(Clone)]
+pub(crate) struct Config {
+    pub(crate) lat: f64,
+    pub(crate) lon: f64,
+    pub(crate) tz: Tz,
+    // outbound notification spool
+    pub(crate) spool_dir: String,
+    // thresholds (static defaults; see `rules` for context-conditional overrides)
+    pub(crate) lis_threshold: u8,
+    pub(crate) g_min_notify: u8,
+    pub(crate) r_min_notify: u8,
+    pub(crate) s_min_notify: u8,
+    pub(crate) short_bz_nt: f64,
+    pub(crate) short_spd_kms: f64,
+    // daily report hour (local)
+    pub(crate) daily_hour: u32,
+    // Email
+    pub(crate) smtp_server: Option<String>,
+    pub(crate) smtp_port: Option<u16>,       // "587" for STARTTLS, "465" for implicit
+    pub(crate) smtp_tls: Option<String>,     // "starttls" (default) or "implicit"
+    pub(crate) smtp_user: Option<String>,
+    pub(crate) smtp_pass: Option<String>,
+    pub(crate) email_from: Option<String>,
+    pub(crate) email_to: Option<String>,
+    // Twilio
+    pub(crate) twilio_sid: Option<String>,
+    pub(crate) twilio_token: Option<String>,
+    pub(crate) twilio_from: Option<String>,
+    pub(crate) sms_to: Option<String>,
+    // Webhook
+    pub(crate) webhook_urls: Vec<String>,
+    pub(crate) webhook_auth_header: Option<String>,
+    // context-conditional threshold rules, loaded from CONFIG_PATH
+    pub(crate) rules: Vec<ThresholdRule>,
+    // per-(channel, alert_class) send rate limits, e.g. "3/1h"
+    pub(crate) rate_limits: HashMap<String, RateLimit>,
+    pub(crate) throttle: Arc<Throttle>,
+    // correlation ids for tracing an alert through logs/spool/webhook
+    pub(crate) snowflake: Arc<Snowflake>,
+    // shared trajectory state all pollers feed and the daily outlook reads
+    pub(crate) state: Arc<StateMachine>,
+}
+
+// ---------- Context-conditional threshold rules ----------
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub(crate) struct ThresholdOverrides {
+    pub(crate) lis_threshold: Option<u8>,
+    pub(crate) g_min_notify: Option<u8>,
+    pub(crate) r_min_notify: Option<u8>,
+    pub(crate) s_min_notify: Option<u8>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ThresholdRule {
+    #[serde(rename = "if")]
+    pub(crate) cond: Option<String>,
+    pub(crate) then: Option<ThresholdOverrides>,
+    #[serde(rename = "else")]
+    pub(crate) else_: Option<ThresholdOverrides>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct FileConfig {
+    #[serde(default)]
+    rules: Vec<ThresholdRule>,
+}
+
+/// Runtime facts a rule's `if` condition is evaluated against. Every field
+/// must be filled in; a call site without a real value for one (e.g. `kp`)
+/// passes a placeholder such as 0.0, and conditions on that field are then
+/// evaluated against the placeholder.
+pub(crate) struct RuleContext {
+    pub(crate) daylight: bool,
+    pub(crate) kp: f64,
+    pub(crate) g: u8,
+    pub(crate) r: u8,
+    pub(crate) s: u8,
+    pub(crate) hour: u32,
+}
+
+/// Effective thresholds after walking the rule list for a given context.
+pub(crate) struct Thresholds {
+    pub(crate) lis_threshold: u8,
+    pub(crate) g_min_notify: u8,
+    pub(crate) r_min_notify: u8,
+    pub(crate) s_min_notify: u8,
+}
+
+impl Thresholds {
+    fn apply(&mut self, o: &ThresholdOverrides) {
+        if let Some(v) = o.lis_threshold {
+            self.lis_threshold = v;
+        }
+        if let Some(v) = o.g_min_notify {
+            self.g_min_notify = v;
+        }
+        if let Some(v) = o.r_min_notify {
+            self.r_min_notify = v;
+        }
+        if let Some(v) = o.s_min_notify {
+            self.s_min_notify = v;
+        }
+    }
+}
+
+// Tiny condition grammar: `daylight`, `!daylight`, or `<field> <op> <num>`
+// with field in {kp, g, r, s, hour} and op in {>=, <=, ==, >, <}. Anything
+// that doesn't parse is treated as never matching rather than an error, so a
+// typo in the config file degrades to "rule ignored" instead of a crash loop.
+fn eval_condition(cond: &str, ctx: &RuleContext) -> bool {
+    let cond = cond.trim();
+    if cond == "daylight" {
+        return ctx.daylight;
+    }
+    if cond == "!daylight" {
+        return !ctx.daylight;
+    }
+
+    for op in ["==", ">=", "<=", ">", "<"] {
+        if let Some((field, rhs)) = cond.split_once(op) {
+            let field = field.trim();
+            let Ok(rhs) = rhs.trim().parse::<f64>() else {
+                return false;
+            };
+            let lhs = match field {
+                "kp" => ctx.kp,
+                "g" => ctx.g as f64,
+                "r" => ctx.r as f64,
+                "s" => ctx.s as f64,
+                "hour" => ctx.hour as f64,
+                _ => return false,
+            };
+            return match op {
+                "==" => (lhs - rhs).abs() < f64::EPSILON,
+                ">=" => lhs >= rhs,
+                "<=" => lhs <= rhs,
+                ">" => lhs > rhs,
+                "<" => lhs < rhs,
+                _ => false,
+            };
+        }
+    }
+    false
+}
+
+impl Config {
+    pub(crate) fn from_env() -> Self {
+        let tz: Tz = env::var("LOCAL_TZ")
+            .unwrap_or_else(|_| "America/New_York".to_string())
+            .parse()
+            .unwrap_or(chrono_tz::America::New_York);
+
+        let rules = env::var("CONFIG_PATH")
+            .ok()
+            .and_then(|path| match std::fs::read_to_string(&path) {
+                Ok(text) => match toml::from_str::<FileConfig>(&text) {
+                    Ok(fc) => Some(fc.rules),
+                    Err(e) => {
+                        tracing::warn!(config_path = %path, error = %e, "failed to parse CONFIG_PATH, ignoring");
+                        None
+                    }
+                },
+                Err(e) => {
+                    tracing::warn!(config_path = %path, error = %e, "failed to read CONFIG_PATH, ignoring");
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        let mut rate_limits = HashMap::new();
+        for (channel, env_key, default) in [
+            ("email", "EMAIL_RATE", "10/1h"),
+            ("sms", "SMS_RATE", "3/1h"),
+            ("webhook", "WEBHOOK_RATE", "20/1h"),
+        ] {
+            let spec = env::var(env_key).unwrap_or_else(|_| default.to_string());
+            match RateLimit::parse(&spec) {
+                Some(limit) => {
+                    rate_limits.insert(channel.to_string(), limit);
+                }
+                None => tracing::warn!(channel, spec, "invalid rate limit spec, channel is unthrottled"),
+            }
+        }
+
+        Self {
+            lat: env::var("LAT").ok().and_then(|v| v.parse().ok()).unwrap_or(28.9),
+            lon: env::var("LON").ok().and_then(|v| v.parse().ok()).unwrap_or(-81.3),
+            tz,
+            spool_dir: env::var("SPOOL_DIR").unwrap_or_else(|_| "./spool".to_string()),
+            lis_threshold: env::var("LIS_THRESHOLD").ok().and_then(|v| v.parse().ok()).unwrap_or(40),
+            g_min_notify: env::var("G_MIN_NOTIFY").ok().and_then(|v| v.parse().ok()).unwrap_or(2),
+            r_min_notify: env::var("R_MIN_NOTIFY").ok().and_then(|v| v.parse().ok()).unwrap_or(2),
+            s_min_notify: env::var("S_MIN_NOTIFY").ok().and_then(|v| v.parse().ok()).unwrap_or(2),
+            short_bz_nt: env::var("SHORT_BZ_NT").ok().and_then(|v| v.parse().ok()).unwrap_or(-10.0),
+            short_spd_kms: env::var("SHORT_SPD_KMS").ok().and_then(|v| v.parse().ok()).unwrap_or(600.0),
+            daily_hour: env::var("DAILY_REPORT_HOUR").ok().and_then(|v| v.parse().ok()).unwrap_or(7),
+            smtp_server: env::var("SMTP_SERVER").ok(),
+            smtp_port: env::var("SMTP_PORT").ok().and_then(|v| v.parse().ok()),
+            smtp_tls: env::var("SMTP_TLS").ok(),
+            smtp_user: env::var("SMTP_USERNAME").ok(),
+            smtp_pass: env::var("SMTP_PASSWORD").ok(),
+            email_from: env::var("EMAIL_FROM").ok(),
+            email_to: env::var("EMAIL_TO").ok(),
+            twilio_sid: env::var("TWILIO_ACCOUNT_SID").ok(),
+            twilio_token: env::var("TWILIO_AUTH_TOKEN").ok(),
+            twilio_from: env::var("TWILIO_FROM").ok(),
+            sms_to: env::var("SMS_TO").ok(),
+            webhook_urls: env::var("WEBHOOK_URLS")
+                .ok()
+                .map(|v| v.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+                .unwrap_or_default(),
+            webhook_auth_header: env::var("WEBHOOK_AUTH_HEADER").ok(),
+            rules,
+            rate_limits,
+            throttle: Arc::new(Throttle::new()),
+            snowflake: Arc::new(Snowflake::new(
+                env::var("MACHINE_ID").ok().and_then(|v| v.parse().ok()).unwrap_or(0),
+            )),
+            state: Arc::new(StateMachine::new()),
+        }
+    }
+
+    pub(crate) fn want_email(&self) -> bool {
+        self.smtp_server.is_some() && self.smtp_user.is_some() && self.smtp_pass.is_some()
+            && self.email_from.is_some() && self.email_to.is_some()
+    }
+    pub(crate) fn want_sms(&self) -> bool {
+        self.twilio_sid.is_some() && self.twilio_token.is_some()
+            && self.twilio_from.is_some() && self.sms_to.is_some()
+    }
+
+    /// Walk `rules` against `ctx`, overlaying every matching `if` rule's
+    /// `then` in order. If none matched, overlay every rule's `else`
+    /// (standalone or attached to an `if` rule) instead. Falls back to the static env-derived fields when there are
+    /// no rules at all (no CONFIG_PATH, or nothing matched and no `else`).
+    pub(crate) fn effective_thresholds(&self, ctx: &RuleContext) -> Thresholds {
+        let mut out = Thresholds {
+            lis_threshold: self.lis_threshold,
+            g_min_notify: self.g_min_notify,
+            r_min_notify: self.r_min_notify,
+            s_min_notify: self.s_min_notify,
+        };
+
+        let mut matched = false;
+        for rule in &self.rules {
+            if let Some(cond) = &rule.cond {
+                if eval_condition(cond, ctx) {
+                    matched = true;
+                    if let Some(then) = &rule.then {
+                        out.apply(then);
+                    }
+                }
+            }
+        }
+        if !matched {
+            for rule in &self.rules {
+                if let Some(else_) = &rule.else_ {
+                    out.apply(else_);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(daylight: bool, kp: f64, g: u8, r: u8, s: u8, hour: u32) -> RuleContext {
+        RuleContext { daylight, kp, g, r, s, hour }
+    }
+
+    #[test]
+    fn daylight_keywords() {
+        let c = ctx(true, 0.0, 0, 0, 0, 12);
+        assert!(eval_condition("daylight", &c));
+        assert!(!eval_condition("!daylight", &c));
+
+        let c = ctx(false, 0.0, 0, 0, 0, 2);
+        assert!(!eval_condition("daylight", &c));
+        assert!(eval_condition("!daylight", &c));
+    }
+
+    #[test]
+    fn comparison_operators() {
+        let c = ctx(false, 6.0, 3, 1, 2, 14);
+        assert!(eval_condition("kp >= 6", &c));
+        assert!(!eval_condition("kp >= 7", &c));
+        assert!(eval_condition("kp <= 6", &c));
+        assert!(eval_condition("kp == 6", &c));
+        assert!(eval_condition("g > 2", &c));
+        assert!(eval_condition("r < 2", &c));
+        assert!(eval_condition("s == 2", &c));
+        assert!(eval_condition("hour >= 14", &c));
+    }
+
+    #[test]
+    fn unparseable_conditions_never_match() {
+        let c = ctx(true, 6.0, 3, 1, 2, 14);
+        assert!(!eval_condition("kp >= not-a-number", &c));
+        assert!(!eval_condition("bogus_field >= 1", &c));
+        assert!(!eval_condition("kp", &c)); // missing operator entirely
+    }
+
+    #[test]
+    fn effective_thresholds_overlays_matching_rules_in_order() {
+        let mut cfg = Config::from_env();
+        cfg.lis_threshold = 40;
+        cfg.rules = vec![
+            ThresholdRule {
+                cond: Some("daylight".to_string()),
+                then: Some(ThresholdOverrides { lis_threshold: Some(50), ..Default::default() }),
+                else_: None,
+            },
+            ThresholdRule {
+                cond: Some("kp >= 7".to_string()),
+                then: Some(ThresholdOverrides { g_min_notify: Some(1), ..Default::default() }),
+                else_: None,
+            },
+        ];
+
+        let out = cfg.effective_thresholds(&ctx(true, 8.0, 0, 0, 0, 12));
+        assert_eq!(out.lis_threshold, 50); // daylight rule applied
+        assert_eq!(out.g_min_notify, 1); // kp rule also applied, on top
+
+        let out = cfg.effective_thresholds(&ctx(false, 8.0, 0, 0, 0, 2));
+        assert_eq!(out.lis_threshold, 40); // daylight rule didn't match, no override
+        assert_eq!(out.g_min_notify, 1);
+    }
+
+    #[test]
+    fn effective_thresholds_falls_back_to_else_when_nothing_matched() {
+        let mut cfg = Config::from_env();
+        cfg.lis_threshold = 40;
+        cfg.rules = vec![ThresholdRule {
+            cond: Some("kp >= 9".to_string()),
+            then: Some(ThresholdOverrides { lis_threshold: Some(80), ..Default::default() }),
+            else_: Some(ThresholdOverrides { lis_threshold: Some(20), ..Default::default() }),
+        }];
+
+        let out = cfg.effective_thresholds(&ctx(false, 3.0, 0, 0, 0, 2));
+        assert_eq!(out.lis_threshold, 20);
+    }
+}
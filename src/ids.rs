@@ -0,0 +1,52 @@
+// Correlation IDs, so every alert can be traced through logs, the spool, and
+// whatever's on the other end of a webhook. Event deduplication used to live
+// here too (a content-fingerprint `Dedup`), but `StateMachine` now owns
+// "don't repeat an already-announced state" directly off the condition that
+// actually matters — the state transition, not raw (level, g, r, s) values,
+// which couldn't tell a repeat apart from a genuine second storm onset with
+// the same signature.
+use chrono::Utc;
+use std::sync::Mutex;
+
+const CUSTOM_EPOCH_MS: i64 = 1_700_000_000_000; // 2023-11-14, arbitrary recent epoch
+const MACHINE_BITS: u32 = 10;
+const SEQ_BITS: u32 = 12;
+const MAX_SEQ: u64 = (1 << SEQ_BITS) - 1;
+
+/// Twitter-Snowflake-style monotonic ID: <ms since custom epoch><machine
+/// id><sequence>. One generator per process; `MACHINE_ID` disambiguates
+/// multiple watcher instances sharing a spool or log aggregator.
+pub(crate) struct Snowflake {
+    machine_id: u64,
+    state: Mutex<(i64, u64)>, // (last_ms, seq)
+}
+
+impl Snowflake {
+    pub(crate) fn new(machine_id: u64) -> Self {
+        Self {
+            machine_id: machine_id & ((1 << MACHINE_BITS) - 1),
+            state: Mutex::new((0, 0)),
+        }
+    }
+
+    pub(crate) fn next_id(&self) -> String {
+        let mut state = self.state.lock().unwrap();
+        let (last_ms, seq) = &mut *state;
+        let mut now = Utc::now().timestamp_millis();
+        if now < *last_ms {
+            now = *last_ms; // clock moved backwards; never emit a lower id
+        }
+        let mut next_seq = if now == *last_ms { (*seq + 1) & MAX_SEQ } else { 0 };
+        if now == *last_ms && next_seq == 0 {
+            now += 1; // sequence exhausted within this millisecond, borrow the next one
+            next_seq = 0;
+        }
+        *last_ms = now;
+        *seq = next_seq;
+
+        let id = ((now - CUSTOM_EPOCH_MS) as u64) << (MACHINE_BITS + SEQ_BITS)
+            | (self.machine_id << SEQ_BITS)
+            | next_seq;
+        id.to_string()
+    }
+}
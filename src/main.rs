@@ -4,113 +4,72 @@ use chrono_tz::Tz;
 use regex::Regex;
 use reqwest::Client;
 use serde_json::Value;
-use std::{env, time::Duration as StdDuration};
+use std::time::Duration as StdDuration;
+use tokio::sync::mpsc;
 use tokio::time::{interval, sleep};
 
 // Bring the async transport trait into scope so `.send().await` compiles.
 use lettre::AsyncTransport;
 
+mod config;
+mod ids;
+mod notify;
+mod spool;
+mod state;
+mod telemetry;
+mod throttle;
+
+use config::{Config, RuleContext};
+use notify::NotifyContext;
+
 // NOAA endpoints
 const KP_URL: &str = "https://services.swpc.noaa.gov/products/noaa-planetary-k-index-forecast.json";
 const ALERTS_URL: &str = "https://services.swpc.noaa.gov/products/alerts.json";
 const BZ_URL: &str = "https://services.swpc.noaa.gov/json/rtsw/rtsw_mag_1m.json";
 const SPD_URL: &str = "https://services.swpc.noaa.gov/json/rtsw/rtsw_speed_1m.json";
 
-#[derive(Clone)]
-struct Config {
-    lat: f64,
-    lon: f64,
-    tz: Tz,
-    // thresholds
-    lis_threshold: u8,
-    g_min_notify: u8,
-    r_min_notify: u8,
-    s_min_notify: u8,
-    short_bz_nt: f64,
-    short_spd_kms: f64,
-    // daily report hour (local)
-    daily_hour: u32,
-    // Email
-    smtp_server: Option<String>,
-    smtp_port: Option<u16>,       // "587" for STARTTLS, "465" for implicit
-    smtp_tls: Option<String>,     // "starttls" (default) or "implicit"
-    smtp_user: Option<String>,
-    smtp_pass: Option<String>,
-    email_from: Option<String>,
-    email_to: Option<String>,
-    // Twilio
-    twilio_sid: Option<String>,
-    twilio_token: Option<String>,
-    twilio_from: Option<String>,
-    sms_to: Option<String>,
-}
-
-impl Config {
-    fn from_env() -> Self {
-        let tz: Tz = env::var("LOCAL_TZ")
-            .unwrap_or_else(|_| "America/New_York".to_string())
-            .parse()
-            .unwrap_or(chrono_tz::America::New_York);
-
-        Self {
-            lat: env::var("LAT").ok().and_then(|v| v.parse().ok()).unwrap_or(28.9),
-            lon: env::var("LON").ok().and_then(|v| v.parse().ok()).unwrap_or(-81.3),
-            tz,
-            lis_threshold: env::var("LIS_THRESHOLD").ok().and_then(|v| v.parse().ok()).unwrap_or(40),
-            g_min_notify: env::var("G_MIN_NOTIFY").ok().and_then(|v| v.parse().ok()).unwrap_or(2),
-            r_min_notify: env::var("R_MIN_NOTIFY").ok().and_then(|v| v.parse().ok()).unwrap_or(2),
-            s_min_notify: env::var("S_MIN_NOTIFY").ok().and_then(|v| v.parse().ok()).unwrap_or(2),
-            short_bz_nt: env::var("SHORT_BZ_NT").ok().and_then(|v| v.parse().ok()).unwrap_or(-10.0),
-            short_spd_kms: env::var("SHORT_SPD_KMS").ok().and_then(|v| v.parse().ok()).unwrap_or(600.0),
-            daily_hour: env::var("DAILY_REPORT_HOUR").ok().and_then(|v| v.parse().ok()).unwrap_or(7),
-            smtp_server: env::var("SMTP_SERVER").ok(),
-            smtp_port: env::var("SMTP_PORT").ok().and_then(|v| v.parse().ok()),
-            smtp_tls: env::var("SMTP_TLS").ok(),
-            smtp_user: env::var("SMTP_USERNAME").ok(),
-            smtp_pass: env::var("SMTP_PASSWORD").ok(),
-            email_from: env::var("EMAIL_FROM").ok(),
-            email_to: env::var("EMAIL_TO").ok(),
-            twilio_sid: env::var("TWILIO_ACCOUNT_SID").ok(),
-            twilio_token: env::var("TWILIO_AUTH_TOKEN").ok(),
-            twilio_from: env::var("TWILIO_FROM").ok(),
-            sms_to: env::var("SMS_TO").ok(),
-        }
-    }
-    fn want_email(&self) -> bool {
-        self.smtp_server.is_some() && self.smtp_user.is_some() && self.smtp_pass.is_some()
-            && self.email_from.is_some() && self.email_to.is_some()
-    }
-    fn want_sms(&self) -> bool {
-        self.twilio_sid.is_some() && self.twilio_token.is_some()
-            && self.twilio_from.is_some() && self.sms_to.is_some()
-    }
-}
-
 #[tokio::main]
 async fn main() {
+    let _log_guard = telemetry::init();
+
     let cfg = Config::from_env();
     let client = Client::builder().user_agent("spaceweather-watcher/0.2").build().unwrap();
 
     // 1) Startup baseline report
     match build_full_status(&client, &cfg).await {
-        Ok((lis, level, text)) => {
-            let subject = format!("Space Weather Startup Baseline: {} (LIS {})", level, lis.round());
-            let _ = send_notifications(&cfg, &subject, &text).await;
-            println!("Startup baseline sent: {}", subject);
+        Ok(status) => {
+            let subject = format!(
+                "Space Weather Startup Baseline: {} (LIS {}) [{}]",
+                status.level, status.lis.round(), status.ctx.correlation_id
+            );
+            notify::send_notifications(&cfg, &subject, &status.body, &status.ctx).await;
+            tracing::info!(task = "startup_baseline", lis = status.lis, level = %status.level, correlation_id = %status.ctx.correlation_id, "startup baseline sent");
         }
-        Err(e) => eprintln!("Startup baseline error: {e}"),
+        Err(e) => tracing::error!(task = "startup_baseline", error = %e, "startup baseline failed"),
     }
 
-    // 2) Launch periodic tasks with different cadences
+    // 2) Launch periodic tasks with different cadences, all feeding one
+    // event channel into the trajectory state machine.
+    let (sample_tx, sample_rx) = mpsc::channel::<Status>(32);
+    {
+        let cfg_clone = cfg.clone();
+        tokio::spawn(async move { spool::run_spool_task(cfg_clone).await }); // drains spool/retries
+    }
+    {
+        let cfg_clone = cfg.clone();
+        tokio::spawn(async move { run_state_task(cfg_clone, sample_rx).await }); // owns trajectory state
+    }
     {
         let cfg_clone = cfg.clone();
         let client_clone = client.clone();
-        tokio::spawn(async move { poll_rtsw_task(client_clone, cfg_clone).await }); // 60s
+        let tx_clone = sample_tx.clone();
+        tokio::spawn(async move { poll_rtsw_task(client_clone, cfg_clone, tx_clone).await }); // 60s
     }
     {
         let cfg_clone = cfg.clone();
         let client_clone = client.clone();
-        tokio::spawn(async move { poll_alerts_task(client_clone, cfg_clone).await }); // 5m
+        let tx_clone = sample_tx.clone();
+        tokio::spawn(async move { poll_alerts_task(client_clone, cfg_clone, tx_clone).await }); // 5m
     }
     {
         let cfg_clone = cfg.clone();
@@ -123,46 +82,61 @@ async fn main() {
 }
 
 // ---------- Schedulers ----------
-async fn poll_rtsw_task(client: Client, cfg: Config) {
-    let mut last_short_sent: i64 = 0;
-    let mut last_lis_sent: i64 = 0;
+
+/// Owns the shared `WatcherState` machine: every sample from every poller
+/// passes through here, and this is the only place that decides whether a
+/// state change is worth telling anyone about.
+async fn run_state_task(cfg: Config, mut rx: mpsc::Receiver<Status>) {
+    while let Some(status) = rx.recv().await {
+        // Every sample reaches the machine unconditionally, and the machine
+        // is the sole authority on whether a transition is worth a
+        // notification — it already suppresses "no change" and re-announces
+        // on genuine re-entry. A second gate here would risk committing the
+        // state change while still dropping the notification for it, with
+        // no way to retry.
+        let Some(entered) = cfg.state.observe(status.lis, status.lis_threshold, status.short_flag) else {
+            continue;
+        };
+        let subject = format!(
+            "Space Weather [{}]: {} (LIS {}) [{}]",
+            entered, status.level, status.lis.round(), status.ctx.correlation_id
+        );
+        spool::enqueue(&cfg, &subject, &status.body, &status.ctx).await;
+        tracing::info!(
+            task = "state_machine",
+            state = %entered,
+            lis = status.lis,
+            level = %status.level,
+            correlation_id = %status.ctx.correlation_id,
+            "state transition notified"
+        );
+    }
+}
+
+async fn poll_rtsw_task(client: Client, cfg: Config, tx: mpsc::Sender<Status>) {
     let mut intv = interval(StdDuration::from_secs(60));
     loop {
         intv.tick().await;
-        if let Ok((lis, level, text, short_flag)) = build_quick_status(&client, &cfg).await {
-            let now = Utc::now().timestamp();
-            let can_send_short = now - last_short_sent >= 10 * 60; // 10 min cooldown
-            let can_send_lis = now - last_lis_sent >= 30 * 60;     // 30 min cooldown
-            if short_flag && can_send_short {
-                let subject = format!("Space Weather: {} (LIS {})", level, lis.round());
-                let _ = send_notifications(&cfg, &subject, &text).await;
-                println!("Short-fuse warn sent: {}", subject);
-                last_short_sent = now;
-            } else if lis >= cfg.lis_threshold as f64 && can_send_lis {
-                let subject = format!("Space Weather: {} (LIS {})", level, lis.round());
-                let _ = send_notifications(&cfg, &subject, &text).await;
-                println!("LIS warn sent: {}", subject);
-                last_lis_sent = now;
-            }
+        if let Ok(status) = build_quick_status(&client, &cfg).await {
+            let _ = tx.send(status).await;
         }
     }
 }
 
-async fn poll_alerts_task(client: Client, cfg: Config) {
+async fn poll_alerts_task(client: Client, cfg: Config, tx: mpsc::Sender<Status>) {
     let mut intv = interval(StdDuration::from_secs(300)); // 5 min
-    let mut last_levels: (u8, u8, u8) = (0, 0, 0);
     loop {
         intv.tick().await;
         if let Ok((g, r, s)) = fetch_alert_levels(&client).await {
-            if g >= cfg.g_min_notify || r >= cfg.r_min_notify || s >= cfg.s_min_notify {
-                if (g, r, s) != last_levels {
-                    let (_lis, _lvl, body) = summarize_for_email(&client, &cfg)
-                        .await
-                        .unwrap_or((0.0, "Low".into(), "".into()));
-                    let subject = format!("SWPC Alerts: G{} R{} S{}", g, r, s);
-                    let _ = send_notifications(&cfg, &subject, &body).await;
-                    println!("Alert-level change sent: {}", subject);
-                    last_levels = (g, r, s);
+            let daylight = is_daylight_local(Utc::now(), cfg.tz, 7, 19);
+            let hour = Utc::now().with_timezone(&cfg.tz).hour();
+            // kp isn't fetched on this loop's cadence, so kp-based rules are evaluated against kp = 0.0 here.
+            let thresholds = cfg.effective_thresholds(&RuleContext { daylight, kp: 0.0, g, r, s, hour });
+            // Whether this is worth telling anyone is the state machine's
+            // call, not this poller's — it just reports every crossing.
+            if g >= thresholds.g_min_notify || r >= thresholds.r_min_notify || s >= thresholds.s_min_notify {
+                if let Ok(status) = summarize_for_email(&client, &cfg).await {
+                    let _ = tx.send(status).await;
                 }
             }
         }
@@ -192,26 +166,37 @@ async fn daily_report_scheduler(client: &Client, cfg: &Config) {
         };
         let sleep_for = target_local.with_timezone(&Utc) - Utc::now();
         let dur = sleep_for.to_std().unwrap_or(StdDuration::from_secs(0));
-        println!("Next daily report at {}", target_local);
+        tracing::info!(task = "daily_report", next_run = %target_local, "daily report scheduled");
         sleep(dur).await;
 
-        if let Ok((_lis, _lvl, text)) = build_full_status(client, cfg).await {
+        if let Ok(status) = build_full_status(client, cfg).await {
             let subject = format!(
-                "Daily Space Weather Outlook — {}",
-                target_local.format("%Y-%m-%d")
+                "Daily Space Weather Outlook — {} [{}]",
+                target_local.format("%Y-%m-%d"),
+                status.ctx.correlation_id
             );
-            let _ = send_notifications(cfg, &subject, &text).await;
-            println!("Daily report sent: {}", subject);
+            spool::enqueue(cfg, &subject, &status.body, &status.ctx).await;
+            tracing::info!(task = "daily_report", lis = status.lis, level = %status.level, correlation_id = %status.ctx.correlation_id, "daily report spooled");
         }
         sleep(StdDuration::from_secs(24 * 3600)).await;
     }
 }
 
 // ---------- Status builders ----------
-async fn build_quick_status(
-    client: &Client,
-    cfg: &Config,
-) -> Result<(f64, String, String, bool), String> {
+
+/// A single evaluated observation: the rendered report plus everything a
+/// notification channel (including the spool) might need to send or act on
+/// it.
+struct Status {
+    lis: f64,
+    level: String,
+    body: String,
+    short_flag: bool,
+    lis_threshold: u8,
+    ctx: NotifyContext,
+}
+
+async fn build_quick_status(client: &Client, cfg: &Config) -> Result<Status, String> {
     let (kp, bz, spd) = tokio::try_join!(
         fetch_kp_max24(client),
         fetch_latest_value(client, BZ_URL, "bz_gsm"),
@@ -221,6 +206,9 @@ async fn build_quick_status(
 
     let (g, r, s) = fetch_alert_levels(client).await.map_err(|e| e.to_string())?;
     let daylight = is_daylight_local(Utc::now(), cfg.tz, 7, 19);
+    let hour = Utc::now().with_timezone(&cfg.tz).hour();
+
+    let thresholds = cfg.effective_thresholds(&RuleContext { daylight, kp, g, r, s, hour });
 
     let (lis, level, _diag, short) = score_local(
         cfg.lat,
@@ -235,24 +223,55 @@ async fn build_quick_status(
         cfg.short_spd_kms,
     );
 
-    let body = format_report(cfg, lis, &level, kp, bz, spd, g, r, s, daylight);
-    Ok((lis, level, body, short))
+    let correlation_id = cfg.snowflake.next_id();
+    let body = format_report(
+        cfg,
+        &correlation_id,
+        lis,
+        &level,
+        kp,
+        bz,
+        spd,
+        g,
+        r,
+        s,
+        daylight,
+        thresholds.lis_threshold,
+    );
+    let ctx = NotifyContext {
+        correlation_id,
+        lis,
+        level: level.clone(),
+        severity: notify::severity_for(&level),
+        kp,
+        bz,
+        spd,
+        g,
+        r,
+        s,
+    };
+
+    Ok(Status {
+        lis,
+        level,
+        body,
+        short_flag: short,
+        lis_threshold: thresholds.lis_threshold,
+        ctx,
+    })
 }
 
-async fn build_full_status(client: &Client, cfg: &Config) -> Result<(f64, String, String), String> {
-    let (lis, level, body, _short) = build_quick_status(client, cfg).await?;
-    Ok((lis, level, body))
+async fn build_full_status(client: &Client, cfg: &Config) -> Result<Status, String> {
+    build_quick_status(client, cfg).await
 }
 
-async fn summarize_for_email(
-    client: &Client,
-    cfg: &Config,
-) -> Result<(f64, String, String), String> {
+async fn summarize_for_email(client: &Client, cfg: &Config) -> Result<Status, String> {
     build_full_status(client, cfg).await
 }
 
 fn format_report(
     cfg: &Config,
+    correlation_id: &str,
     lis: f64,
     level: &str,
     kp: f64,
@@ -262,11 +281,14 @@ fn format_report(
     r: u8,
     s: u8,
     daylight: bool,
+    effective_lis_threshold: u8,
 ) -> String {
     let now_local: DateTime<Tz> = Utc::now().with_timezone(&cfg.tz);
     format!(
-        "Space Weather Status — {}\n\nLocal Impact Score: {} ({})\n\nInputs:\n  • Kp (max next 24h): {:.1}\n  • L1 Bz: {:?} nT\n  • L1 Speed: {:?} km/s\n  • Alerts — G:{}  R:{}  S:{}\n  • Daylight now: {}\n\nGuidance:\n  • LIS ≥ {} triggers warnings (configurable).\n  • Short-fuse trigger: Bz ≤ {} nT & Speed ≥ {} km/s (≈15–60 min lead).\n",
+        "Space Weather Status — {} [{}]\n\nTrajectory: {}\nLocal Impact Score: {} ({})\n\nInputs:\n  • Kp (max next 24h): {:.1}\n  • L1 Bz: {:?} nT\n  • L1 Speed: {:?} km/s\n  • Alerts — G:{}  R:{}  S:{}\n  • Daylight now: {}\n\nGuidance:\n  • LIS ≥ {} triggers warnings (configurable, rule-adjusted).\n  • Short-fuse trigger: Bz ≤ {} nT & Speed ≥ {} km/s (≈15–60 min lead).\n",
         now_local.format("%Y-%m-%d %H:%M %Z"),
+        correlation_id,
+        cfg.state.current(),
         lis.round(),
         level,
         kp,
@@ -276,7 +298,7 @@ fn format_report(
         r,
         s,
         daylight,
-        cfg.lis_threshold,
+        effective_lis_threshold,
         cfg.short_bz_nt,
         cfg.short_spd_kms
     )
@@ -284,8 +306,13 @@ fn format_report(
 
 // ---------- Fetchers ----------
 async fn fetch_kp_max24(client: &Client) -> Result<f64, reqwest::Error> {
-    let txt = client.get(KP_URL).send().await?.text().await?;
-    let v: Value = serde_json::from_str(&txt).unwrap_or(Value::Null);
+    let resp = client.get(KP_URL).send().await?;
+    let status = resp.status();
+    let txt = resp.text().await?;
+    let v: Value = serde_json::from_str(&txt).unwrap_or_else(|e| {
+        tracing::warn!(endpoint = KP_URL, http_status = status.as_u16(), error = %e, "Kp forecast parse failed");
+        Value::Null
+    });
 
     let now = Utc::now();
     let end = now + Duration::hours(24);
@@ -330,7 +357,12 @@ async fn fetch_latest_value(
     url: &str,
     key: &str,
 ) -> Result<Option<f64>, reqwest::Error> {
-    let v: Value = client.get(url).send().await?.json().await.unwrap_or(Value::Null);
+    let resp = client.get(url).send().await?;
+    let status = resp.status();
+    let v: Value = resp.json().await.unwrap_or_else(|e| {
+        tracing::warn!(endpoint = url, http_status = status.as_u16(), error = %e, key, "fetch_latest_value parse failed");
+        Value::Null
+    });
     if let Value::Array(rows) = v {
         for row in rows.iter().rev() {
             if let Value::Object(map) = row {
@@ -351,7 +383,12 @@ async fn fetch_latest_value(
 }
 
 async fn fetch_alert_levels(client: &Client) -> Result<(u8, u8, u8), reqwest::Error> {
-    let v: Value = client.get(ALERTS_URL).send().await?.json().await.unwrap_or(Value::Null);
+    let resp = client.get(ALERTS_URL).send().await?;
+    let status = resp.status();
+    let v: Value = resp.json().await.unwrap_or_else(|e| {
+        tracing::warn!(endpoint = ALERTS_URL, http_status = status.as_u16(), error = %e, "alerts parse failed");
+        Value::Null
+    });
     let (mut g, mut r, mut s) = (0u8, 0u8, 0u8);
 
     let re_g = Regex::new(r"G([1-5])").unwrap();
@@ -449,6 +486,8 @@ fn score_local(
     }
     .to_string();
 
+    tracing::debug!(lis, level = %level, kp = kp_max24, bz = ?bz, spd = ?spd, g, r, s, "scored local impact");
+
     (
         lis,
         level,
@@ -466,18 +505,8 @@ fn is_daylight_local(now_utc: DateTime<Utc>, tz: Tz, start_h: u32, end_h: u32) -
 }
 
 // ---------- Notifications ----------
-async fn send_notifications(cfg: &Config, subject: &str, body: &str) {
-    if cfg.want_email() {
-        if let Err(e) = send_email(cfg, subject, body).await {
-            eprintln!("Email send error: {e}");
-        }
-    }
-    if cfg.want_sms() {
-        if let Err(e) = send_sms_twilio(cfg, &format!("{subject}\n{body}")).await {
-            eprintln!("SMS send error: {e}");
-        }
-    }
-}
+// Channel selection lives in `notify` (NotificationChannel + send_notifications);
+// this module only owns the actual email/SMS transport calls.
 
 // Build SMTP transport with selectable TLS mode/port.
 fn build_mailer(
@@ -510,7 +539,7 @@ fn build_mailer(
     Ok(mailer)
 }
 
-async fn send_email(
+pub(crate) async fn send_email(
     cfg: &Config,
     subject: &str,
     body: &str,
@@ -540,7 +569,7 @@ async fn send_email(
         .map_err(|e| format!("SMTP send failed: {e:?}").into())
 }
 
-async fn send_sms_twilio(cfg: &Config, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub(crate) async fn send_sms_twilio(cfg: &Config, body: &str) -> Result<(), Box<dyn std::error::Error>> {
     let sid = cfg.twilio_sid.clone().unwrap();
     let token = cfg.twilio_token.clone().unwrap();
     let from = cfg.twilio_from.clone().unwrap();
@@ -0,0 +1,187 @@
+// Outbound notification channels. `NotificationChannel` is the extension
+// point: email and Twilio SMS are the original two, webhook is a third,
+// user-configured one, and the durable spool drives the same trait objects
+// so adding a channel here is the only thing a future integration needs.
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::{send_email, send_sms_twilio};
+
+/// Everything a channel might want to put in a payload beyond the rendered
+/// subject/body text — the raw inputs behind the score, not just the score.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct NotifyContext {
+    pub(crate) correlation_id: String,
+    pub(crate) lis: f64,
+    pub(crate) level: String,
+    pub(crate) severity: u8,
+    pub(crate) kp: f64,
+    pub(crate) bz: Option<f64>,
+    pub(crate) spd: Option<f64>,
+    pub(crate) g: u8,
+    pub(crate) r: u8,
+    pub(crate) s: u8,
+}
+
+pub(crate) fn severity_for(level: &str) -> u8 {
+    match level {
+        "Severe" => 5,
+        "High" => 4,
+        "Moderate" => 3,
+        "Elevated" => 2,
+        _ => 1,
+    }
+}
+
+#[async_trait]
+pub(crate) trait NotificationChannel: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// Disambiguates multiple instances of the same channel kind, e.g. which
+    /// of several configured webhook URLs this is. `None` for singleton
+    /// channels like email and SMS.
+    fn target(&self) -> Option<String> {
+        None
+    }
+    async fn send(&self, subject: &str, body: &str, ctx: &NotifyContext) -> Result<(), String>;
+}
+
+pub(crate) struct EmailChannel {
+    cfg: Config,
+}
+
+#[async_trait]
+impl NotificationChannel for EmailChannel {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+    async fn send(&self, subject: &str, body: &str, _ctx: &NotifyContext) -> Result<(), String> {
+        send_email(&self.cfg, subject, body).await.map_err(|e| e.to_string())
+    }
+}
+
+pub(crate) struct SmsChannel {
+    cfg: Config,
+}
+
+#[async_trait]
+impl NotificationChannel for SmsChannel {
+    fn name(&self) -> &'static str {
+        "sms"
+    }
+    async fn send(&self, subject: &str, body: &str, _ctx: &NotifyContext) -> Result<(), String> {
+        send_sms_twilio(&self.cfg, &format!("{subject}\n{body}"))
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    subject: &'a str,
+    body: &'a str,
+    correlation_id: &'a str,
+    lis: f64,
+    level: &'a str,
+    severity: u8,
+    kp: f64,
+    bz: Option<f64>,
+    spd: Option<f64>,
+    g: u8,
+    r: u8,
+    s: u8,
+}
+
+pub(crate) struct WebhookChannel {
+    pub(crate) url: String,
+    pub(crate) auth_header: Option<String>,
+}
+
+#[async_trait]
+impl NotificationChannel for WebhookChannel {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+    fn target(&self) -> Option<String> {
+        Some(self.url.clone())
+    }
+    async fn send(&self, subject: &str, body: &str, ctx: &NotifyContext) -> Result<(), String> {
+        let payload = WebhookPayload {
+            subject,
+            body,
+            correlation_id: &ctx.correlation_id,
+            lis: ctx.lis,
+            level: &ctx.level,
+            severity: ctx.severity,
+            kp: ctx.kp,
+            bz: ctx.bz,
+            spd: ctx.spd,
+            g: ctx.g,
+            r: ctx.r,
+            s: ctx.s,
+        };
+
+        let client = reqwest::Client::new();
+        let mut req = client.post(&self.url).json(&payload);
+        if let Some(h) = &self.auth_header {
+            req = req.header("Authorization", h);
+        }
+
+        let resp = req.send().await.map_err(|e| e.to_string())?;
+        let status = resp.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            let text = resp.text().await.unwrap_or_default();
+            Err(format!("webhook {} returned {}: {}", self.url, status, text))
+        }
+    }
+}
+
+/// Build the set of channels currently configured, in the same order
+/// `send_notifications` has always dispatched them: email, then SMS, then
+/// any webhooks.
+pub(crate) fn channels_for(cfg: &Config) -> Vec<Box<dyn NotificationChannel>> {
+    let mut channels: Vec<Box<dyn NotificationChannel>> = Vec::new();
+    if cfg.want_email() {
+        channels.push(Box::new(EmailChannel { cfg: cfg.clone() }));
+    }
+    if cfg.want_sms() {
+        channels.push(Box::new(SmsChannel { cfg: cfg.clone() }));
+    }
+    for url in &cfg.webhook_urls {
+        channels.push(Box::new(WebhookChannel {
+            url: url.clone(),
+            auth_header: cfg.webhook_auth_header.clone(),
+        }));
+    }
+    channels
+}
+
+/// Fan a notification out to every configured channel, logging each
+/// outcome. Used for the immediate (non-spooled) startup baseline send.
+/// Each channel is rate-limited by (channel, alert_class) before dispatch —
+/// the one place all the anti-spam logic now lives, instead of being
+/// scattered per poller.
+pub(crate) async fn send_notifications(cfg: &Config, subject: &str, body: &str, ctx: &NotifyContext) {
+    for channel in channels_for(cfg) {
+        let name = channel.name();
+        if let Some(limit) = cfg.rate_limits.get(name) {
+            if !cfg.throttle.try_consume(name, &ctx.level, limit) {
+                tracing::warn!(channel = name, alert_class = %ctx.level, correlation_id = %ctx.correlation_id, outcome = "suppressed", subject, "notification suppressed by rate limit");
+                continue;
+            }
+        }
+        match channel.send(subject, body, ctx).await {
+            Ok(()) => {
+                tracing::info!(channel = name, correlation_id = %ctx.correlation_id, outcome = "sent", subject, "notification sent");
+            }
+            Err(e) => {
+                if let Some(limit) = cfg.rate_limits.get(name) {
+                    cfg.throttle.refund(name, &ctx.level, limit);
+                }
+                tracing::warn!(channel = name, correlation_id = %ctx.correlation_id, outcome = "failed", error = %e, subject, "notification failed")
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// Durable outbound notification queue, modeled on a mail spool: each
+// notification is a JSON file on disk until every channel has delivered it.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration as StdDuration,
+};
+use tokio::time::interval;
+
+use crate::config::Config;
+use crate::notify::{channels_for, NotifyContext};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChannelState {
+    channel: String, // matches a NotificationChannel::name(), e.g. "email", "sms", "webhook"
+    target: Option<String>, // disambiguates multiple instances of one channel (e.g. which webhook URL)
+    delivered: bool,
+    attempts: u32,
+    next_retry: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SpoolItem {
+    id: String,
+    subject: String,
+    body: String,
+    ctx: NotifyContext,
+    created_at: DateTime<Utc>,
+    channels: Vec<ChannelState>,
+}
+
+impl SpoolItem {
+    fn is_done(&self) -> bool {
+        self.channels.iter().all(|c| c.delivered)
+    }
+}
+
+// Monotonic-ish id: wall clock millis + a process-local counter, so items sort
+// by creation order on disk even if several are spooled within the same tick.
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+fn new_item_id() -> String {
+    let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{:06}", Utc::now().timestamp_millis(), seq)
+}
+
+fn backoff_for(attempts: u32) -> chrono::Duration {
+    let secs = match attempts {
+        0 => 60,
+        1 => 5 * 60,
+        2 => 15 * 60,
+        _ => 60 * 60,
+    };
+    chrono::Duration::seconds(secs)
+}
+
+fn item_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{id}.json"))
+}
+
+async fn write_item(dir: &Path, item: &SpoolItem) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    let tmp = item_path(dir, &format!("{}.tmp", item.id));
+    let final_path = item_path(dir, &item.id);
+    let data = serde_json::to_vec_pretty(item).unwrap_or_default();
+    tokio::fs::write(&tmp, data).await?;
+    tokio::fs::rename(&tmp, &final_path).await
+}
+
+/// Spool a notification for durable, retried delivery instead of sending it
+/// inline. Channels are chosen from whatever is configured right now (via
+/// the same `NotificationChannel` set `send_notifications` uses); a channel
+/// that isn't configured is simply not queued.
+pub(crate) async fn enqueue(cfg: &Config, subject: &str, body: &str, ctx: &NotifyContext) {
+    let channels: Vec<ChannelState> = channels_for(cfg)
+        .iter()
+        .map(|ch| ChannelState {
+            channel: ch.name().to_string(),
+            target: ch.target(),
+            delivered: false,
+            attempts: 0,
+            next_retry: Utc::now(),
+        })
+        .collect();
+    if channels.is_empty() {
+        return;
+    }
+
+    let item = SpoolItem {
+        id: new_item_id(),
+        subject: subject.to_string(),
+        body: body.to_string(),
+        ctx: ctx.clone(),
+        created_at: Utc::now(),
+        channels,
+    };
+
+    if let Err(e) = write_item(Path::new(&cfg.spool_dir), &item).await {
+        tracing::error!(task = "spool", item_id = %item.id, error = %e, "spool write failed");
+    }
+}
+
+async fn deliver_channel(cfg: &Config, item: &SpoolItem, ch: &ChannelState) -> Result<(), String> {
+    let channel = channels_for(cfg)
+        .into_iter()
+        .find(|c| c.name() == ch.channel && c.target() == ch.target)
+        .ok_or_else(|| format!("channel {} ({:?}) no longer configured", ch.channel, ch.target))?;
+    channel.send(&item.subject, &item.body, &item.ctx).await
+}
+
+async fn process_item(cfg: &Config, dir: &Path, mut item: SpoolItem) {
+    let now = Utc::now();
+    let mut changed = false;
+
+    for idx in 0..item.channels.len() {
+        if item.channels[idx].delivered || item.channels[idx].next_retry > now {
+            continue;
+        }
+        let ch = item.channels[idx].clone();
+
+        if let Some(limit) = cfg.rate_limits.get(ch.channel.as_str()) {
+            if !cfg.throttle.try_consume(&ch.channel, &item.ctx.level, limit) {
+                tracing::warn!(task = "spool", item_id = %item.id, channel = %ch.channel, outcome = "suppressed", "spooled delivery suppressed by rate limit, left pending");
+                continue;
+            }
+        }
+
+        match deliver_channel(cfg, &item, &ch).await {
+            Ok(()) => {
+                item.channels[idx].delivered = true;
+                changed = true;
+                tracing::info!(task = "spool", item_id = %item.id, channel = %ch.channel, outcome = "sent", "spooled notification delivered");
+            }
+            Err(e) => {
+                if let Some(limit) = cfg.rate_limits.get(ch.channel.as_str()) {
+                    cfg.throttle.refund(&ch.channel, &item.ctx.level, limit);
+                }
+                item.channels[idx].next_retry = now + backoff_for(item.channels[idx].attempts);
+                item.channels[idx].attempts += 1;
+                changed = true;
+                tracing::warn!(
+                    task = "spool",
+                    item_id = %item.id,
+                    channel = %ch.channel,
+                    outcome = "failed",
+                    attempt = item.channels[idx].attempts,
+                    error = %e,
+                    "spooled notification delivery failed, rescheduled"
+                );
+            }
+        }
+    }
+
+    if item.is_done() {
+        if let Err(e) = tokio::fs::remove_file(item_path(dir, &item.id)).await {
+            tracing::error!(task = "spool", item_id = %item.id, error = %e, "spool cleanup failed");
+        }
+    } else if changed {
+        if let Err(e) = write_item(dir, &item).await {
+            tracing::error!(task = "spool", item_id = %item.id, error = %e, "spool update failed");
+        }
+    }
+}
+
+async fn load_items(dir: &Path) -> Vec<SpoolItem> {
+    let mut items = Vec::new();
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(e) => e,
+        Err(_) => return items, // nothing spooled yet
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        if let Ok(data) = tokio::fs::read(&path).await {
+            if let Ok(item) = serde_json::from_slice::<SpoolItem>(&data) {
+                items.push(item);
+            }
+        }
+    }
+    items
+}
+
+/// Background task that drains the spool on a fixed cadence: reload every
+/// pending item from disk, retry whichever channels are due, and persist or
+/// remove the item depending on outcome. Surviving a restart is just "reload
+/// whatever is still on disk", so there is no separate startup path.
+pub(crate) async fn run_spool_task(cfg: Config) {
+    let dir = PathBuf::from(&cfg.spool_dir);
+    let mut intv = interval(StdDuration::from_secs(30));
+    loop {
+        intv.tick().await;
+        for item in load_items(&dir).await {
+            process_item(&cfg, &dir, item).await;
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// Watcher state machine: the three poll tasks used to each track their own
+// cooldown timers and decide independently whether to notify. That made it
+// impossible to express "the storm that was ongoing is now over" — there was
+// no state to fall out of. Every poll task now just reports what it sees;
+// this module is the single place that turns a stream of observations into
+// "what, if anything, should we tell someone".
+use std::fmt;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WatcherState {
+    Quiet,
+    Elevated,
+    ShortFuseWatch,
+    StormOngoing,
+    Recovery,
+}
+
+impl WatcherState {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            WatcherState::Quiet => "Quiet",
+            WatcherState::Elevated => "Elevated",
+            WatcherState::ShortFuseWatch => "ShortFuseWatch",
+            WatcherState::StormOngoing => "StormOngoing",
+            WatcherState::Recovery => "Recovery",
+        }
+    }
+}
+
+impl fmt::Display for WatcherState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Classification is driven by the same `lis_threshold` the rest of the
+// watcher already treats as configurable (static default, or overlaid per
+// context by `Config::effective_thresholds`) — a state machine that ignored
+// it would silently undo that configurability.
+fn classify(lis: f64, lis_threshold: u8, short_flag: bool) -> WatcherState {
+    if short_flag {
+        return WatcherState::ShortFuseWatch;
+    }
+    if lis >= lis_threshold as f64 {
+        WatcherState::StormOngoing
+    } else if lis >= lis_threshold as f64 / 2.0 {
+        WatcherState::Elevated
+    } else {
+        WatcherState::Quiet
+    }
+}
+
+/// One resting state shared by every poller via `Config`. `observe` is the
+/// only way to move it: feed in the latest (lis, threshold, short_flag)
+/// reading and get back the state to notify for, if any.
+pub(crate) struct StateMachine {
+    current: Mutex<WatcherState>,
+}
+
+impl StateMachine {
+    pub(crate) fn new() -> Self {
+        Self { current: Mutex::new(WatcherState::Quiet) }
+    }
+
+    pub(crate) fn current(&self) -> WatcherState {
+        *self.current.lock().unwrap()
+    }
+
+    /// Classify the observation and advance the machine. Returns `None` when
+    /// nothing changed (staying quiet, or staying in an already-announced
+    /// state — this is what makes "stay in StormOngoing" suppress repeats).
+    /// Returns `Some(state)` for the state a notification should announce:
+    /// the state just entered, or `Recovery` when dropping back to quiet
+    /// from anything else. A recovery notification resets the machine to
+    /// `Quiet` immediately, so it's a one-shot announcement, not a resting
+    /// state of its own.
+    pub(crate) fn observe(&self, lis: f64, lis_threshold: u8, short_flag: bool) -> Option<WatcherState> {
+        let observed = classify(lis, lis_threshold, short_flag);
+        let mut current = self.current.lock().unwrap();
+
+        if observed == *current {
+            return None;
+        }
+
+        if *current != WatcherState::Quiet && observed == WatcherState::Quiet {
+            *current = WatcherState::Quiet;
+            return Some(WatcherState::Recovery);
+        }
+
+        *current = observed;
+        Some(observed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_stays_quiet() {
+        let sm = StateMachine::new();
+        assert_eq!(sm.observe(5.0, 40, false), None);
+        assert_eq!(sm.current(), WatcherState::Quiet);
+    }
+
+    #[test]
+    fn entering_a_state_notifies_once_then_suppresses_repeats() {
+        let sm = StateMachine::new();
+        assert_eq!(sm.observe(45.0, 40, false), Some(WatcherState::StormOngoing));
+        assert_eq!(sm.current(), WatcherState::StormOngoing);
+        // same reading again — already announced, no repeat notification
+        assert_eq!(sm.observe(45.0, 40, false), None);
+        assert_eq!(sm.observe(99.0, 40, false), None);
+    }
+
+    #[test]
+    fn short_fuse_takes_priority_over_lis() {
+        let sm = StateMachine::new();
+        assert_eq!(sm.observe(45.0, 40, true), Some(WatcherState::ShortFuseWatch));
+    }
+
+    #[test]
+    fn dropping_to_quiet_announces_recovery_then_rests_in_quiet() {
+        let sm = StateMachine::new();
+        assert_eq!(sm.observe(45.0, 40, false), Some(WatcherState::StormOngoing));
+        assert_eq!(sm.observe(5.0, 40, false), Some(WatcherState::Recovery));
+        assert_eq!(sm.current(), WatcherState::Quiet);
+        // back to quiet and already announced — no further recovery spam
+        assert_eq!(sm.observe(5.0, 40, false), None);
+    }
+
+    #[test]
+    fn a_second_identical_storm_after_recovery_notifies_again() {
+        // Regression for the bug where dedup, applied before the sample ever
+        // reached the state machine, could drop a repeat of an
+        // already-seen fingerprint and leave the machine stuck — here the
+        // machine itself must re-announce on re-entry regardless of how
+        // many times the same (lis, threshold, short_flag) was seen before.
+        let sm = StateMachine::new();
+        assert_eq!(sm.observe(45.0, 40, false), Some(WatcherState::StormOngoing));
+        assert_eq!(sm.observe(5.0, 40, false), Some(WatcherState::Recovery));
+        assert_eq!(sm.observe(45.0, 40, false), Some(WatcherState::StormOngoing));
+    }
+
+    #[test]
+    fn elevated_is_a_distinct_tier_below_the_storm_threshold() {
+        let sm = StateMachine::new();
+        assert_eq!(sm.observe(25.0, 40, false), Some(WatcherState::Elevated));
+        assert_eq!(sm.observe(45.0, 40, false), Some(WatcherState::StormOngoing));
+    }
+}
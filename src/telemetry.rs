@@ -0,0 +1,56 @@
+// Structured tracing setup: human-readable lines to stdout by default, or
+// newline-delimited JSON to a file, selected purely by env so operators can
+// switch formats without a redeploy.
+//
+//   LOG_FORMAT=json|plain   (default: plain)
+//   LOG_FILE=<path>         (default: stdout)
+//   LOG_LEVEL=<tracing filter, e.g. info, debug, watcher=debug>
+
+use std::env;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::EnvFilter;
+
+fn filter() -> EnvFilter {
+    let level = env::var("LOG_LEVEL").unwrap_or_else(|_| "info".to_string());
+    EnvFilter::try_new(&level).unwrap_or_else(|_| EnvFilter::new("info"))
+}
+
+/// Install the global tracing subscriber. Returns a guard that must be kept
+/// alive for the process lifetime when logging to a file (it owns the
+/// background flush thread); dropping it early would silently stop log
+/// delivery.
+pub(crate) fn init() -> Option<WorkerGuard> {
+    let json = env::var("LOG_FORMAT").map(|v| v == "json").unwrap_or(false);
+
+    match env::var("LOG_FILE") {
+        Ok(path) => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .unwrap_or_else(|e| panic!("failed to open LOG_FILE {path}: {e}"));
+            let (writer, guard) = tracing_appender::non_blocking(file);
+            if json {
+                tracing_subscriber::fmt()
+                    .with_env_filter(filter())
+                    .with_writer(writer)
+                    .json()
+                    .init();
+            } else {
+                tracing_subscriber::fmt()
+                    .with_env_filter(filter())
+                    .with_writer(writer)
+                    .init();
+            }
+            Some(guard)
+        }
+        Err(_) => {
+            if json {
+                tracing_subscriber::fmt().with_env_filter(filter()).json().init();
+            } else {
+                tracing_subscriber::fmt().with_env_filter(filter()).init();
+            }
+            None
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Per-(channel, alert_class) rate limiting. Backed by a sharded concurrent
+// map so every poller/task can check and record sends without a global
+// lock — the single point the old code was missing, since `last_short_sent`
+// and `last_lis_sent` only ever covered one task's two alert kinds.
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RateLimit {
+    capacity: f64,
+    per: chrono::Duration,
+}
+
+impl RateLimit {
+    /// Parse a spec like "3/1h" (3 sends per hour) or "10/30m".
+    pub(crate) fn parse(spec: &str) -> Option<Self> {
+        let (count, window) = spec.trim().split_once('/')?;
+        let capacity: f64 = count.trim().parse().ok()?;
+        let window = window.trim();
+        if window.len() < 2 {
+            return None;
+        }
+        let (num, unit) = window.split_at(window.len() - 1);
+        let num: i64 = num.parse().ok()?;
+        let per = match unit {
+            "s" => chrono::Duration::seconds(num),
+            "m" => chrono::Duration::minutes(num),
+            "h" => chrono::Duration::hours(num),
+            "d" => chrono::Duration::days(num),
+            _ => return None,
+        };
+        Some(Self { capacity, per })
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: DateTime<Utc>,
+}
+
+/// Sharded (channel, alert_class) -> token bucket map. Cheap to clone
+/// (wrapped in an `Arc` by `Config`) so every spawned task shares one view.
+pub(crate) struct Throttle {
+    buckets: DashMap<(String, String), Bucket>,
+}
+
+impl Throttle {
+    pub(crate) fn new() -> Self {
+        Self { buckets: DashMap::new() }
+    }
+
+    /// Refill, check, and (if allowed) reserve one unit of allowance, all
+    /// under the single shard lock `DashMap::entry` holds for the duration
+    /// of the closure. Two concurrent callers for the same key can no longer
+    /// both observe a token available before either consumes one — whichever
+    /// gets the shard lock first either takes the last token or doesn't.
+    /// The reservation is provisional: callers whose attempt doesn't
+    /// actually deliver must call `refund` so a failing channel doesn't burn
+    /// through its whole budget without ever sending anything.
+    pub(crate) fn try_consume(&self, channel: &str, alert_class: &str, limit: &RateLimit) -> bool {
+        let key = (channel.to_string(), alert_class.to_string());
+        let now = Utc::now();
+        let mut entry = self
+            .buckets
+            .entry(key)
+            .or_insert_with(|| Bucket { tokens: limit.capacity, last_refill: now });
+
+        let elapsed_ms = (now - entry.last_refill).num_milliseconds().max(0) as f64;
+        let window_ms = limit.per.num_milliseconds().max(1) as f64;
+        if elapsed_ms > 0.0 {
+            let refilled = limit.capacity * (elapsed_ms / window_ms);
+            entry.tokens = (entry.tokens + refilled).min(limit.capacity);
+            entry.last_refill = now;
+        }
+
+        if entry.tokens >= 1.0 {
+            entry.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Give back the unit `try_consume` reserved for an attempt that never
+    /// actually delivered (e.g. a 5xx from the channel). Without this, a
+    /// channel that's failing outright burns its whole budget on attempts
+    /// and locks out real deliveries for the rest of the window even though
+    /// nothing was ever sent.
+    pub(crate) fn refund(&self, channel: &str, alert_class: &str, limit: &RateLimit) {
+        let key = (channel.to_string(), alert_class.to_string());
+        if let Some(mut entry) = self.buckets.get_mut(&key) {
+            entry.tokens = (entry.tokens + 1.0).min(limit.capacity);
+        }
+    }
+}